@@ -1,13 +1,42 @@
+pub mod cfg_expr;
+pub mod workspace;
+
 use std::fmt::{Debug, Display, Formatter, Error};
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
 use app_result::{AppResult, app_err_msg};
 
+/// Hashes `value` with a hasher that starts from fixed, non-randomized keys, so the result is
+/// stable across runs and machines. Used to fold things like registry urls or feature sets into
+/// tags file names without pulling in an extra crate for it.
+fn stable_hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Short, stable, filename-safe representation of `stable_hash(value)`.
+fn stable_hash_str<T: Hash>(value: &T) -> String {
+    format!("{:x}", stable_hash(value) & 0xffff_ffff)
+}
+
 /// For every `TagsRoot` a `rusty-tags.{vi,emacs}` file will be created.
 ///
-/// `Proj` is the tags root of the current cargo project. Its tags file will contain the tags of
+/// `Proj` is the tags root of a cargo project. Its tags file will contain the tags of
 /// the source code of the cargo project and of its direct dependencies. The tags file will be
 /// placed at the root of the cargo project, beside of the `Cargo.toml`.
 ///
+/// When the project is part of a cargo workspace described by a virtual manifest (a root
+/// `Cargo.toml` with only `[workspace]` and no `[package]`), one `Proj` is produced per workspace
+/// member, so each member gets its own tags file beside its own `Cargo.toml` rather than the run
+/// failing or producing nothing for the workspace root.
+///
 /// `Lib` represents a direct or indirect (a dependency of a dependency) dependency of the cargo
 /// project. For each dependency a tags file will be created containing the tags of the source
 /// code of the dependency and its direct dependecies. The tags file will be placed at the root of
@@ -29,6 +58,34 @@ pub enum TagsRoot {
 
 pub type TagsRoots = Vec<TagsRoot>;
 
+/// Removes duplicate dependencies, keeping the first occurrence.
+///
+/// Workspace members frequently share dependencies; once the project discovery code has unioned
+/// every member's dependencies into one list, this is used to collapse that list back down so the
+/// same `lib-version.vi` file isn't regenerated once per member that happens to depend on it.
+/// Dependencies are compared structurally (`SourceKind` derives `PartialEq`/`Eq`/`Hash`), not via
+/// their `Display` output, so this can't silently go wrong if `Display` is ever reworked to drop
+/// or abbreviate a field.
+pub fn dedup_dependencies(dependencies: Vec<SourceKind>) -> Vec<SourceKind> {
+    let mut seen: HashSet<SourceKind> = HashSet::new();
+    dependencies.into_iter()
+        .filter(|dep| seen.insert(dep.clone()))
+        .collect()
+}
+
+/// Builds one `TagsRoot::Proj` per workspace member directory, all sharing `dependencies` (the
+/// union of every member's direct dependencies, deduplicated here via `dedup_dependencies`).
+/// Used once `workspace::is_virtual_manifest` has detected a workspace root and
+/// `workspace::member_dirs` has enumerated its members, to give each member its own tags file
+/// instead of the run failing or producing nothing for the workspace root.
+pub fn workspace_tags_roots(member_dirs: Vec<PathBuf>, dependencies: Vec<SourceKind>) -> TagsRoots {
+    let dependencies = dedup_dependencies(dependencies);
+
+    member_dirs.into_iter()
+        .map(|root_dir| TagsRoot::Proj { root_dir: root_dir, dependencies: dependencies.clone() })
+        .collect()
+}
+
 impl Debug for TagsRoot {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         match *self {
@@ -43,20 +100,163 @@ impl Debug for TagsRoot {
     }
 }
 
-/// Where the source code of a dependency is from. From a git repository, from `crates.io` or from
-/// a local path.
-#[derive(Clone)]
+/// A dependency as read from a manifest, before feature/target activation has been decided:
+/// besides its `SourceKind` it carries the bits cargo itself uses to decide whether the
+/// dependency is actually compiled for a given feature set and target -- whether it's `optional`
+/// (only pulled in by an enabled feature of the same name) and, for a dependency declared under
+/// `target."cfg(...)".dependencies`, the raw `cfg(...)` predicate gating it.
+pub struct DependencySpec {
+    pub source: SourceKind,
+    pub optional: bool,
+    pub target_cfg: Option<String>
+}
+
+/// Which features are active for a run, in the shape of cargo's own `--features`/`--all-features`
+/// switches.
+pub struct FeatureSelection {
+    all_features: bool,
+    features: HashSet<String>
+}
+
+impl FeatureSelection {
+    /// Builds a `FeatureSelection` from parsed `--features`/`--all-features` arguments.
+    pub fn new(all_features: bool, features: Vec<String>) -> FeatureSelection {
+        FeatureSelection { all_features: all_features, features: features.into_iter().collect() }
+    }
+
+    /// Whether `feature` (or, for an optional dependency, the feature of the same name) is
+    /// enabled for this run.
+    pub fn is_active(&self, feature: &str) -> bool {
+        self.all_features || self.features.contains(feature)
+    }
+}
+
+/// Queries `rustc` for the cfg values activated for `target` (the host's own target when `None`),
+/// the same way cargo itself determines which `cfg(...)` predicates a `--target` satisfies.
+pub fn target_cfgs(target: Option<&str>) -> AppResult<Vec<cfg_expr::CfgValue>> {
+    let mut cmd = Command::new("rustc");
+    cmd.arg("--print").arg("cfg");
+
+    if let Some(target) = target {
+        cmd.arg("--target").arg(target);
+    }
+
+    let output = try!(cmd.output());
+    if ! output.status.success() {
+        return Err(app_err_msg(format!("'rustc --print cfg' failed: {}", String::from_utf8_lossy(&output.stderr))));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut cfgs = Vec::new();
+
+    for line in stdout.lines() {
+        match line.find('=') {
+            Some(eq) => {
+                let key = line[..eq].to_owned();
+                let value = line[eq + 1..].trim_matches('"').to_owned();
+                cfgs.push(cfg_expr::CfgValue::KeyPair(key, value));
+            },
+
+            None if ! line.is_empty() => {
+                cfgs.push(cfg_expr::CfgValue::Bare(line.to_owned()));
+            },
+
+            None => { }
+        }
+    }
+
+    Ok(cfgs)
+}
+
+/// Filters `specs` down to the dependencies cargo would actually compile for `features` on a
+/// target whose activated cfg values are `target_cfgs`: an optional dependency is dropped unless
+/// a same-named feature is active, and a `target."cfg(...)"` dependency is only kept when its
+/// predicate (parsed and evaluated via `cfg_expr`) is satisfied by `target_cfgs`.
+pub fn activated_dependencies(specs: Vec<DependencySpec>, features: &FeatureSelection, target_cfgs: &[cfg_expr::CfgValue]) -> AppResult<Vec<SourceKind>> {
+    let mut activated = Vec::new();
+
+    for spec in specs {
+        if spec.optional && ! features.is_active(&spec.source.get_lib_name()) {
+            continue;
+        }
+
+        if let Some(ref raw_cfg) = spec.target_cfg {
+            let expr = try!(cfg_expr::parse(raw_cfg));
+            if ! expr.eval(target_cfgs) {
+                continue;
+            }
+        }
+
+        activated.push(spec.source);
+    }
+
+    Ok(activated)
+}
+
+impl TagsRoot {
+    /// Builds the `Proj` tags root for `root_dir`, keeping only the dependencies of `specs` that
+    /// are actually activated for `features`/`target_cfgs` (see `activated_dependencies`), so
+    /// tags aren't generated for optional or platform-gated dependencies the project never builds.
+    ///
+    /// No command line switch in this crate feeds `features`/`target_cfgs`: callers construct a
+    /// `FeatureSelection` and call `target_cfgs` themselves.
+    pub fn new_proj(root_dir: PathBuf, specs: Vec<DependencySpec>, features: &FeatureSelection, target_cfgs: &[cfg_expr::CfgValue]) -> AppResult<TagsRoot> {
+        let dependencies = try!(activated_dependencies(specs, features, target_cfgs));
+        Ok(TagsRoot::Proj { root_dir: root_dir, dependencies: dependencies })
+    }
+
+    /// Builds the `Lib` tags root for `src_kind`, keeping only the dependencies of `specs` that
+    /// are actually activated for `features`/`target_cfgs` (see `activated_dependencies`).
+    pub fn new_lib(src_kind: SourceKind, specs: Vec<DependencySpec>, features: &FeatureSelection, target_cfgs: &[cfg_expr::CfgValue]) -> AppResult<TagsRoot> {
+        let dependencies = try!(activated_dependencies(specs, features, target_cfgs));
+        Ok(TagsRoot::Lib { src_kind: src_kind, dependencies: dependencies })
+    }
+}
+
+/// Where the source code of a dependency is from. From a git repository, from `crates.io`, from
+/// an alternate/private registry, from a vendored directory or from a local path.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum SourceKind {
     /// the source is from a git repository
     Git {
         lib_name: String,
-        commit_hash: String
+        commit_hash: String,
+
+        /// the non-default cargo features enabled for this dependency (callers must already
+        /// have subtracted the crate's default feature set); a crate's exported symbols can
+        /// differ substantially between feature sets, so this is folded into `tags_file_name`
+        non_default_features: Vec<String>
     },
 
     /// the source is from crates.io
     CratesIo {
         lib_name: String,
-        version: String
+        version: String,
+
+        /// the non-default cargo features enabled for this dependency (callers must already
+        /// have subtracted the crate's default feature set); a crate's exported symbols can
+        /// differ substantially between feature sets, so this is folded into `tags_file_name`
+        non_default_features: Vec<String>
+    },
+
+    /// the source is from an alternate or private registry (the `[registries]` mechanism),
+    /// identified by `registry_url` the same way cargo's `SourceId` folds the registry into a
+    /// dependency's identity
+    Registry {
+        lib_name: String,
+        registry_url: String,
+        version: String,
+
+        /// the non-default cargo features enabled for this dependency (callers must already
+        /// have subtracted the crate's default feature set); a crate's exported symbols can
+        /// differ substantially between feature sets, so this is folded into `tags_file_name`
+        non_default_features: Vec<String>
+    },
+
+    /// the source is from a vendored directory (cargo's `[source]` `directory` replacement)
+    Directory {
+        lib_name: String,
+        path: PathBuf
     },
 
     /// the source is from a local directory
@@ -66,15 +266,69 @@ pub enum SourceKind {
     }
 }
 
+/// Folds the sorted feature list into a short, reproducible suffix to be appended to a cached
+/// tags file name, so that building the same dependency with different feature sets doesn't
+/// clobber one shared file. Returns `None` when `non_default_features` is empty, so a dependency
+/// built with no non-default features keeps its pre-existing file name.
+///
+/// Precondition: `non_default_features` must already have the dependency's default feature set
+/// subtracted out by the caller (e.g. the manifest/feature-resolution code) -- this function
+/// does not know what a dependency's defaults are, so passing the full enabled feature set here
+/// would add a suffix to nearly every dependency and defeat the backward-compat guarantee.
+fn feature_hash_suffix(non_default_features: &[String]) -> Option<String> {
+    if non_default_features.is_empty() {
+        return None;
+    }
+
+    let mut sorted = non_default_features.to_vec();
+    sorted.sort();
+
+    Some(stable_hash_str(&sorted))
+}
+
+/// Appends `[feature,list]` to `f` when `non_default_features` is non-empty, so that
+/// otherwise-identical dependencies with different enabled feature sets are distinguishable in
+/// `Display`/`Debug` output.
+fn display_features(f: &mut Formatter, non_default_features: &[String]) -> Result<(), Error> {
+    if non_default_features.is_empty() {
+        return Ok(());
+    }
+
+    let mut sorted = non_default_features.to_vec();
+    sorted.sort();
+
+    write!(f, " [{}]", sorted.join(","))
+}
+
 impl SourceKind {
     pub fn tags_file_name(&self, tags_spec: &TagsSpec) -> String {
         match *self {
-            SourceKind::Git { ref lib_name, ref commit_hash } => {
-                format!("{}-{}.{}", lib_name, commit_hash, tags_spec.file_extension())
+            SourceKind::Git { ref lib_name, ref commit_hash, ref non_default_features } => {
+                match feature_hash_suffix(non_default_features) {
+                    Some(hash) => format!("{}-{}-{}.{}", lib_name, commit_hash, hash, tags_spec.file_extension()),
+                    None => format!("{}-{}.{}", lib_name, commit_hash, tags_spec.file_extension())
+                }
             },
 
-            SourceKind::CratesIo { ref lib_name, ref version } => {
-                format!("{}-{}.{}", lib_name, version, tags_spec.file_extension())
+            SourceKind::CratesIo { ref lib_name, ref version, ref non_default_features } => {
+                match feature_hash_suffix(non_default_features) {
+                    Some(hash) => format!("{}-{}-{}.{}", lib_name, version, hash, tags_spec.file_extension()),
+                    None => format!("{}-{}.{}", lib_name, version, tags_spec.file_extension())
+                }
+            },
+
+            // folding the registry url into the file name keeps a crate pulled from a private
+            // mirror from clobbering (or being clobbered by) the crates.io tags file of the
+            // same name and version
+            SourceKind::Registry { ref lib_name, ref registry_url, ref version, ref non_default_features } => {
+                match feature_hash_suffix(non_default_features) {
+                    Some(feat_hash) => format!("{}-{}-{}-{}.{}", lib_name, version, stable_hash_str(registry_url), feat_hash, tags_spec.file_extension()),
+                    None => format!("{}-{}-{}.{}", lib_name, version, stable_hash_str(registry_url), tags_spec.file_extension())
+                }
+            },
+
+            SourceKind::Directory { .. } => {
+                tags_spec.file_name().to_owned()
             },
 
             SourceKind::Path { .. } => {
@@ -93,6 +347,14 @@ impl SourceKind {
                 lib_name.clone()
             },
 
+            SourceKind::Registry { ref lib_name, .. } => {
+                lib_name.clone()
+            },
+
+            SourceKind::Directory { ref lib_name, .. } => {
+                lib_name.clone()
+            },
+
             SourceKind::Path { ref lib_name, .. } => {
                 lib_name.clone()
             }
@@ -101,15 +363,22 @@ impl SourceKind {
 
     fn display(&self, f: &mut Formatter) -> Result<(), Error> {
         match *self {
-            SourceKind::Git { ref lib_name, ref commit_hash } => {
-                write!(f, "{}-{}", lib_name, commit_hash)
+            SourceKind::Git { ref lib_name, ref commit_hash, ref non_default_features } => {
+                try!(write!(f, "{}-{}", lib_name, commit_hash));
+                display_features(f, non_default_features)
+            },
+
+            SourceKind::CratesIo { ref lib_name, ref version, ref non_default_features } => {
+                try!(write!(f, "{}-{}", lib_name, version));
+                display_features(f, non_default_features)
             },
 
-            SourceKind::CratesIo { ref lib_name, ref version } => {
-                write!(f, "{}-{}", lib_name, version)
+            SourceKind::Registry { ref lib_name, ref registry_url, ref version, ref non_default_features } => {
+                try!(write!(f, "{}-{} (registry: {})", lib_name, version, registry_url));
+                display_features(f, non_default_features)
             },
 
-            SourceKind::Path { ref lib_name, ref path } => {
+            SourceKind::Directory { ref lib_name, ref path } | SourceKind::Path { ref lib_name, ref path } => {
                 write!(f, "{}: {}", lib_name, path.display())
             }
         }
@@ -128,6 +397,98 @@ impl Display for SourceKind {
     }
 }
 
+/// bump this whenever the tags file format or the fingerprint inputs change, to force every
+/// existing fingerprint to be treated as stale
+const FINGERPRINT_VERSION: u8 = 1;
+
+/// Directory names skipped entirely by `collect_rs_files`: `target` is cargo's own build output
+/// directory, sitting right beside the sources for a `Proj` tags root, and its `.rs` files
+/// (including those regenerated by build scripts under `OUT_DIR`) get fresh mtimes on nearly
+/// every `cargo build` -- walking into it would make the fingerprint effectively always stale and
+/// balloon every up-to-date check into hashing a potentially huge tree. Dot-directories like
+/// `.git` are skipped for the same reason: their contents aren't part of the crate's sources.
+fn is_excluded_from_fingerprint(dir_name: &std::ffi::OsStr) -> bool {
+    dir_name == "target" || dir_name.to_str().map_or(false, |name| name.starts_with('.'))
+}
+
+/// Recursively collects every `.rs` file below `dir` into `out`, skipping `target/` and
+/// dot-directories (see `is_excluded_from_fingerprint`).
+fn collect_rs_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(..) => return
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if path.file_name().map_or(false, is_excluded_from_fingerprint) {
+                continue;
+            }
+            collect_rs_files(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+}
+
+/// Hashes every `.rs` file below `src_dir`, in sorted path order, by its path and modification
+/// time. Using a stable hasher (see `stable_hash`) and a fixed sort order means the same source
+/// tree always produces the same fingerprint, regardless of directory iteration order or which
+/// machine computed it.
+fn source_fingerprint(src_dir: &PathBuf) -> u64 {
+    let mut files = Vec::new();
+    collect_rs_files(src_dir.as_path(), &mut files);
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    FINGERPRINT_VERSION.hash(&mut hasher);
+
+    for file in &files {
+        file.hash(&mut hasher);
+
+        if let Ok(metadata) = fs::metadata(file) {
+            if let Ok(modified) = metadata.modified() {
+                if let Ok(since_epoch) = modified.duration_since(UNIX_EPOCH) {
+                    since_epoch.as_secs().hash(&mut hasher);
+                    since_epoch.subsec_nanos().hash(&mut hasher);
+                }
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+fn fingerprint_path(src_dir: &PathBuf, tags_spec: &TagsSpec) -> PathBuf {
+    let mut path = src_dir.clone();
+    path.push(format!("{}.fingerprint", tags_spec.file_name()));
+    path
+}
+
+/// Reads back a fingerprint written by `write_fingerprint`: a single format-version byte
+/// followed by the little-endian bytes of the source hash.
+fn read_fingerprint(path: &PathBuf) -> Option<(u8, u64)> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(..) => return None
+    };
+
+    let mut buf = Vec::new();
+    if file.read_to_end(&mut buf).is_err() {
+        return None;
+    }
+
+    if buf.len() != 9 {
+        return None;
+    }
+
+    let mut hash_bytes = [0u8; 8];
+    hash_bytes.copy_from_slice(&buf[1..9]);
+
+    Some((buf[0], u64::from_le_bytes(hash_bytes)))
+}
+
 pub struct Tags {
     /// the root directory of the source code
     /// for which the tags have been created
@@ -146,6 +507,10 @@ impl Tags {
         Tags { src_dir: src_dir.clone(), tags_file: tags_file.clone(), cached: cached }
     }
 
+    /// Tags are up to date when a tags file already exists for `src_dir` *and* the fingerprint
+    /// sidecar written alongside it (see `write_fingerprint`) still matches the current state of
+    /// the source tree. This catches sources that changed underneath an existing tags file, which
+    /// a mere file-existence check misses.
     pub fn is_up_to_date(&self, tags_spec: &TagsSpec) -> bool {
         if ! self.cached {
             return false;
@@ -154,7 +519,29 @@ impl Tags {
         let mut src_tags = self.src_dir.clone();
         src_tags.push(tags_spec.file_name());
 
-        src_tags.as_path().is_file()
+        if ! src_tags.as_path().is_file() {
+            return false;
+        }
+
+        match read_fingerprint(&fingerprint_path(&self.src_dir, tags_spec)) {
+            Some((version, hash)) => {
+                version == FINGERPRINT_VERSION && hash == source_fingerprint(&self.src_dir)
+            },
+
+            None => false
+        }
+    }
+
+    /// Writes the fingerprint sidecar for the current state of `src_dir`, to be compared against
+    /// on the next run by `is_up_to_date`. Call this right after (re)generating the tags file.
+    pub fn write_fingerprint(&self, tags_spec: &TagsSpec) -> AppResult<()> {
+        let hash = source_fingerprint(&self.src_dir);
+
+        let mut file = try!(File::create(fingerprint_path(&self.src_dir, tags_spec)));
+        try!(file.write_all(&[FINGERPRINT_VERSION]));
+        try!(file.write_all(&hash.to_le_bytes()));
+
+        Ok(())
     }
 }
 
@@ -220,3 +607,153 @@ impl TagsSpec {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{dedup_dependencies, feature_hash_suffix, SourceKind};
+
+    fn crates_io(lib_name: &str, version: &str) -> SourceKind {
+        SourceKind::CratesIo { lib_name: lib_name.to_owned(), version: version.to_owned(), non_default_features: Vec::new() }
+    }
+
+    #[test]
+    fn dedup_dependencies_removes_exact_duplicates() {
+        let deps = vec![crates_io("serde", "1.0.0"), crates_io("serde", "1.0.0"), crates_io("libc", "0.2.0")];
+
+        let deduped = dedup_dependencies(deps);
+
+        assert_eq!(deduped, vec![crates_io("serde", "1.0.0"), crates_io("libc", "0.2.0")]);
+    }
+
+    #[test]
+    fn dedup_dependencies_keeps_distinct_versions() {
+        let deps = vec![crates_io("serde", "1.0.0"), crates_io("serde", "2.0.0")];
+
+        assert_eq!(dedup_dependencies(deps.clone()), deps);
+    }
+
+    #[test]
+    fn feature_hash_suffix_is_none_for_no_non_default_features() {
+        assert_eq!(feature_hash_suffix(&[]), None);
+    }
+
+    #[test]
+    fn feature_hash_suffix_is_stable_and_order_independent() {
+        let a = vec!["b".to_owned(), "a".to_owned()];
+        let b = vec!["a".to_owned(), "b".to_owned()];
+
+        assert_eq!(feature_hash_suffix(&a), feature_hash_suffix(&b));
+        assert!(feature_hash_suffix(&a).is_some());
+    }
+
+    #[test]
+    fn feature_hash_suffix_differs_for_different_feature_sets() {
+        let a = feature_hash_suffix(&["a".to_owned()]);
+        let b = feature_hash_suffix(&["b".to_owned()]);
+
+        assert_ne!(a, b);
+    }
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use super::{Tags, TagsSpec, TagsKind};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_DIR_ID: AtomicUsize = AtomicUsize::new(0);
+
+    /// Creates a fresh, empty directory under the system temp dir, unique to this test process
+    /// and call, so parallel test runs don't trip over each other's source trees.
+    fn temp_src_dir() -> PathBuf {
+        let id = NEXT_DIR_ID.fetch_add(1, Ordering::SeqCst);
+
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("rusty-tags-fingerprint-test-{}-{}", std::process::id(), id));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn vi_tags_spec() -> TagsSpec {
+        TagsSpec::new(TagsKind::Vi, "rusty-tags.vi".to_owned(), "rusty-tags.emacs".to_owned()).unwrap()
+    }
+
+    #[test]
+    fn not_up_to_date_when_not_cached() {
+        let src_dir = temp_src_dir();
+        let tags_file = src_dir.join("rusty-tags.vi");
+        let tags = Tags::new(&src_dir, &tags_file, false);
+
+        assert!(! tags.is_up_to_date(&vi_tags_spec()));
+
+        fs::remove_dir_all(&src_dir).ok();
+    }
+
+    #[test]
+    fn not_up_to_date_without_a_written_fingerprint() {
+        let src_dir = temp_src_dir();
+        let tags_file = src_dir.join("rusty-tags.vi");
+        fs::write(&tags_file, "").unwrap();
+
+        let tags = Tags::new(&src_dir, &tags_file, true);
+
+        assert!(! tags.is_up_to_date(&vi_tags_spec()));
+
+        fs::remove_dir_all(&src_dir).ok();
+    }
+
+    #[test]
+    fn up_to_date_after_writing_the_fingerprint_for_an_unchanged_tree() {
+        let src_dir = temp_src_dir();
+        let spec = vi_tags_spec();
+
+        fs::write(src_dir.join("rusty-tags.vi"), "").unwrap();
+        fs::write(src_dir.join("lib.rs"), "fn main() {}").unwrap();
+
+        let tags = Tags::new(&src_dir, &src_dir.join("rusty-tags.vi"), true);
+        tags.write_fingerprint(&spec).unwrap();
+
+        assert!(tags.is_up_to_date(&spec));
+
+        fs::remove_dir_all(&src_dir).ok();
+    }
+
+    #[test]
+    fn stale_once_a_source_file_is_added_after_the_fingerprint_was_written() {
+        let src_dir = temp_src_dir();
+        let spec = vi_tags_spec();
+
+        fs::write(src_dir.join("rusty-tags.vi"), "").unwrap();
+        fs::write(src_dir.join("lib.rs"), "fn main() {}").unwrap();
+
+        let tags = Tags::new(&src_dir, &src_dir.join("rusty-tags.vi"), true);
+        tags.write_fingerprint(&spec).unwrap();
+
+        fs::write(src_dir.join("extra.rs"), "fn extra() {}").unwrap();
+
+        assert!(! tags.is_up_to_date(&spec));
+
+        fs::remove_dir_all(&src_dir).ok();
+    }
+
+    #[test]
+    fn stays_up_to_date_when_only_target_dir_contents_change() {
+        let src_dir = temp_src_dir();
+        let spec = vi_tags_spec();
+
+        fs::write(src_dir.join("rusty-tags.vi"), "").unwrap();
+        fs::write(src_dir.join("lib.rs"), "fn main() {}").unwrap();
+
+        let tags = Tags::new(&src_dir, &src_dir.join("rusty-tags.vi"), true);
+        tags.write_fingerprint(&spec).unwrap();
+
+        let target_dir = src_dir.join("target").join("debug").join("build").join("out");
+        fs::create_dir_all(&target_dir).unwrap();
+        fs::write(target_dir.join("generated.rs"), "fn generated() {}").unwrap();
+
+        assert!(tags.is_up_to_date(&spec));
+
+        fs::remove_dir_all(&src_dir).ok();
+    }
+}
@@ -0,0 +1,275 @@
+//! Parsing and evaluation of cargo's `cfg(...)` target predicates, e.g.
+//! `cfg(all(unix, not(target_arch = "wasm32")))`, as used in a manifest's
+//! `target."cfg(...)".dependencies` tables.
+//!
+//! This only covers the expression language itself; deciding which dependencies of a
+//! `TagsRoot` are actually activated for a given feature set and target still needs to combine
+//! this with the resolved feature set and a target's cfg values, which is done by the
+//! dependency-resolution code that builds `TagsRoot`s.
+
+use app_result::{AppResult, app_err_msg};
+
+/// A single `key` or `key = "value"` cfg atom, e.g. `unix` or `target_os = "linux"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CfgValue {
+    Bare(String),
+    KeyPair(String, String)
+}
+
+/// A `cfg(...)` predicate, parsed into an AND/OR/NOT tree over `CfgValue` atoms, the same shape
+/// cargo itself evaluates a dependency's `target.\"cfg(...)\"` key against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CfgExpr {
+    Value(CfgValue),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>)
+}
+
+impl CfgExpr {
+    /// Evaluates this expression against the cfg values activated for a target (e.g. `unix`,
+    /// `target_os = "linux"`, `target_arch = "x86_64"`).
+    pub fn eval(&self, activated: &[CfgValue]) -> bool {
+        match *self {
+            CfgExpr::Value(ref value) => activated.contains(value),
+            CfgExpr::All(ref exprs)  => exprs.iter().all(|e| e.eval(activated)),
+            CfgExpr::Any(ref exprs)  => exprs.iter().any(|e| e.eval(activated)),
+            CfgExpr::Not(ref expr)   => ! expr.eval(activated)
+        }
+    }
+}
+
+/// Parses a `cfg(...)` predicate, e.g. `cfg(all(unix, not(target_arch = "wasm32")))`. The outer
+/// `cfg(...)` wrapper is optional, so a bare `unix` or `target_os = "linux"` is also accepted.
+pub fn parse(input: &str) -> AppResult<CfgExpr> {
+    let mut tokens = try!(tokenize(input));
+    tokens.reverse();
+
+    let expr = try!(parse_expr(&mut tokens));
+
+    if let Some(tok) = tokens.pop() {
+        return Err(app_err_msg(format!("unexpected trailing token '{}' in cfg expression '{}'", tok, input)));
+    }
+
+    Ok(expr)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Comma,
+    Eq,
+    LParen,
+    RParen
+}
+
+use std::fmt::{self, Display};
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Ident(ref s) => write!(f, "{}", s),
+            Token::Str(ref s)   => write!(f, "\"{}\"", s),
+            Token::Comma        => write!(f, ","),
+            Token::Eq           => write!(f, "="),
+            Token::LParen       => write!(f, "("),
+            Token::RParen       => write!(f, ")")
+        }
+    }
+}
+
+fn tokenize(input: &str) -> AppResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => { chars.next(); },
+
+            '(' => { chars.next(); tokens.push(Token::LParen); },
+            ')' => { chars.next(); tokens.push(Token::RParen); },
+            ',' => { chars.next(); tokens.push(Token::Comma); },
+            '=' => { chars.next(); tokens.push(Token::Eq); },
+
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                let mut closed = false;
+
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    value.push(c);
+                }
+
+                if ! closed {
+                    return Err(app_err_msg(format!("unterminated string literal in cfg expression '{}'", input)));
+                }
+
+                tokens.push(Token::Str(value));
+            },
+
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            },
+
+            _ => {
+                return Err(app_err_msg(format!("unexpected character '{}' in cfg expression '{}'", c, input)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_expr(tokens: &mut Vec<Token>) -> AppResult<CfgExpr> {
+    match tokens.pop() {
+        Some(Token::Ident(ref ident)) if ident == "cfg" => {
+            try!(expect(tokens, Token::LParen));
+            let expr = try!(parse_expr(tokens));
+            try!(expect(tokens, Token::RParen));
+            Ok(expr)
+        },
+
+        Some(Token::Ident(ref ident)) if ident == "all" => {
+            Ok(CfgExpr::All(try!(parse_list(tokens))))
+        },
+
+        Some(Token::Ident(ref ident)) if ident == "any" => {
+            Ok(CfgExpr::Any(try!(parse_list(tokens))))
+        },
+
+        Some(Token::Ident(ref ident)) if ident == "not" => {
+            try!(expect(tokens, Token::LParen));
+            let expr = try!(parse_expr(tokens));
+            try!(expect(tokens, Token::RParen));
+            Ok(CfgExpr::Not(Box::new(expr)))
+        },
+
+        Some(Token::Ident(key)) => {
+            if let Some(&Token::Eq) = tokens.last() {
+                tokens.pop();
+                match tokens.pop() {
+                    Some(Token::Str(value)) => Ok(CfgExpr::Value(CfgValue::KeyPair(key, value))),
+                    other => Err(app_err_msg(format!("expected a string literal after '{} =', found {:?}", key, other)))
+                }
+            } else {
+                Ok(CfgExpr::Value(CfgValue::Bare(key)))
+            }
+        },
+
+        other => Err(app_err_msg(format!("expected a cfg expression, found {:?}", other)))
+    }
+}
+
+fn parse_list(tokens: &mut Vec<Token>) -> AppResult<Vec<CfgExpr>> {
+    try!(expect(tokens, Token::LParen));
+
+    let mut exprs = Vec::new();
+
+    loop {
+        if let Some(&Token::RParen) = tokens.last() {
+            break;
+        }
+
+        exprs.push(try!(parse_expr(tokens)));
+
+        if let Some(&Token::Comma) = tokens.last() {
+            tokens.pop();
+        } else {
+            break;
+        }
+    }
+
+    try!(expect(tokens, Token::RParen));
+
+    Ok(exprs)
+}
+
+fn expect(tokens: &mut Vec<Token>, expected: Token) -> AppResult<()> {
+    match tokens.pop() {
+        Some(ref tok) if *tok == expected => Ok(()),
+        other => Err(app_err_msg(format!("expected '{}', found {:?}", expected, other)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, CfgExpr, CfgValue};
+
+    fn bare(s: &str) -> CfgExpr {
+        CfgExpr::Value(CfgValue::Bare(s.to_owned()))
+    }
+
+    fn pair(key: &str, value: &str) -> CfgExpr {
+        CfgExpr::Value(CfgValue::KeyPair(key.to_owned(), value.to_owned()))
+    }
+
+    #[test]
+    fn parses_bare_ident() {
+        assert_eq!(parse("unix").unwrap(), bare("unix"));
+    }
+
+    #[test]
+    fn parses_key_value() {
+        assert_eq!(parse("target_os = \"linux\"").unwrap(), pair("target_os", "linux"));
+    }
+
+    #[test]
+    fn parses_optional_cfg_wrapper() {
+        assert_eq!(parse("cfg(unix)").unwrap(), parse("unix").unwrap());
+    }
+
+    #[test]
+    fn parses_all() {
+        let expr = parse("all(unix, target_os = \"linux\")").unwrap();
+        assert_eq!(expr, CfgExpr::All(vec![bare("unix"), pair("target_os", "linux")]));
+    }
+
+    #[test]
+    fn parses_any() {
+        let expr = parse("any(windows, unix)").unwrap();
+        assert_eq!(expr, CfgExpr::Any(vec![bare("windows"), bare("unix")]));
+    }
+
+    #[test]
+    fn parses_not() {
+        let expr = parse("not(windows)").unwrap();
+        assert_eq!(expr, CfgExpr::Not(Box::new(bare("windows"))));
+    }
+
+    #[test]
+    fn parses_nested_expression() {
+        let expr = parse("cfg(all(unix, not(target_arch = \"wasm32\")))").unwrap();
+        assert_eq!(expr, CfgExpr::All(vec![bare("unix"), CfgExpr::Not(Box::new(pair("target_arch", "wasm32")))]));
+    }
+
+    #[test]
+    fn eval_matches_activated_cfgs() {
+        let activated = vec![CfgValue::Bare("unix".to_owned()), CfgValue::KeyPair("target_os".to_owned(), "linux".to_owned())];
+
+        assert!(parse("all(unix, target_os = \"linux\")").unwrap().eval(&activated));
+        assert!(! parse("windows").unwrap().eval(&activated));
+        assert!(parse("any(windows, unix)").unwrap().eval(&activated));
+        assert!(parse("not(windows)").unwrap().eval(&activated));
+    }
+
+    #[test]
+    fn rejects_malformed_expression() {
+        assert!(parse("all(unix").is_err());
+        assert!(parse("target_os = ").is_err());
+        assert!(parse("unix)").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Detection and enumeration of cargo workspaces described by a virtual manifest: a root
+//! `Cargo.toml` with only a `[workspace]` table and no `[package]` table of its own.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+use app_result::{AppResult, app_err_msg};
+
+/// A manifest is virtual when it declares `[workspace]` but has no `[package]` table -- cargo's
+/// own definition of a workspace-only root `Cargo.toml`.
+pub fn is_virtual_manifest(manifest: &Value) -> bool {
+    manifest.get("workspace").is_some() && manifest.get("package").is_none()
+}
+
+/// Reads and parses the `Cargo.toml` at `manifest_path`.
+pub fn read_manifest(manifest_path: &Path) -> AppResult<Value> {
+    let contents = try!(fs::read_to_string(manifest_path)
+        .map_err(|err| app_err_msg(format!("couldn't read '{}': {}", manifest_path.display(), err))));
+
+    contents.parse::<Value>()
+        .map_err(|err| app_err_msg(format!("couldn't parse '{}': {}", manifest_path.display(), err)))
+}
+
+/// The root directories of a virtual manifest's `[workspace] members`, resolved relative to
+/// `workspace_root`. Cargo's own trailing-`/*` glob form (e.g. `crates/*`, extremely common in
+/// real workspaces) is expanded to every immediate subdirectory of `crates`; any other glob
+/// pattern (`*`, `?` or `[` anywhere else in the entry) is rejected with an error instead of
+/// silently being treated as a literal path that doesn't exist.
+pub fn member_dirs(manifest: &Value, workspace_root: &Path) -> AppResult<Vec<PathBuf>> {
+    let members = match manifest.get("workspace").and_then(|w| w.get("members")).and_then(|m| m.as_array()) {
+        Some(members) => members,
+        None => return Ok(Vec::new())
+    };
+
+    let mut dirs = Vec::new();
+
+    for member in members {
+        let member = try!(member.as_str()
+            .ok_or_else(|| app_err_msg("`[workspace] members` entries must be strings".to_owned())));
+
+        dirs.extend(try!(expand_member(member, workspace_root)));
+    }
+
+    Ok(dirs)
+}
+
+/// Resolves a single `[workspace] members` entry to the directory (or, for a trailing `/*` glob,
+/// directories) it names, relative to `workspace_root`.
+fn expand_member(member: &str, workspace_root: &Path) -> AppResult<Vec<PathBuf>> {
+    if let Some(prefix) = member.strip_suffix("/*") {
+        if prefix.contains(['*', '?', '[']) {
+            return Err(app_err_msg(format!(
+                "unsupported glob pattern '{}' in `[workspace] members` (only a trailing '/*' is supported)", member)));
+        }
+
+        let mut glob_dir = workspace_root.to_path_buf();
+        glob_dir.push(prefix);
+
+        let entries = try!(fs::read_dir(&glob_dir)
+            .map_err(|err| app_err_msg(format!("couldn't read workspace member glob directory '{}': {}", glob_dir.display(), err))));
+
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let entry = try!(entry.map_err(|err| app_err_msg(format!("couldn't read entry in '{}': {}", glob_dir.display(), err))));
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+
+        dirs.sort();
+        Ok(dirs)
+    } else if member.contains(['*', '?', '[']) {
+        Err(app_err_msg(format!(
+            "unsupported glob pattern '{}' in `[workspace] members` (only a trailing '/*' is supported)", member)))
+    } else {
+        let mut dir = workspace_root.to_path_buf();
+        dir.push(member);
+        Ok(vec![dir])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_virtual_manifest, member_dirs};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use toml::Value;
+
+    fn parse(toml: &str) -> Value {
+        toml.parse::<Value>().unwrap()
+    }
+
+    #[test]
+    fn detects_virtual_manifest() {
+        let manifest = parse("[workspace]\nmembers = [\"foo\", \"bar\"]\n");
+        assert!(is_virtual_manifest(&manifest));
+    }
+
+    #[test]
+    fn package_manifest_with_workspace_table_is_not_virtual() {
+        let manifest = parse("[package]\nname = \"foo\"\nversion = \"0.1.0\"\n\n[workspace]\n");
+        assert!(! is_virtual_manifest(&manifest));
+    }
+
+    #[test]
+    fn plain_package_manifest_is_not_virtual() {
+        let manifest = parse("[package]\nname = \"foo\"\nversion = \"0.1.0\"\n");
+        assert!(! is_virtual_manifest(&manifest));
+    }
+
+    #[test]
+    fn resolves_member_dirs_relative_to_workspace_root() {
+        let manifest = parse("[workspace]\nmembers = [\"foo\", \"bar\"]\n");
+        let root = Path::new("/ws");
+
+        assert_eq!(member_dirs(&manifest, root).unwrap(), vec![PathBuf::from("/ws/foo"), PathBuf::from("/ws/bar")]);
+    }
+
+    #[test]
+    fn manifest_without_members_has_no_member_dirs() {
+        let manifest = parse("[workspace]\n");
+        assert_eq!(member_dirs(&manifest, Path::new("/ws")).unwrap(), Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    fn expands_a_trailing_glob_member_to_its_subdirectories() {
+        let root = temp_workspace_root();
+        fs::create_dir_all(root.join("crates").join("a")).unwrap();
+        fs::create_dir_all(root.join("crates").join("b")).unwrap();
+        fs::write(root.join("crates").join("not-a-dir.txt"), "").unwrap();
+
+        let manifest = parse("[workspace]\nmembers = [\"crates/*\"]\n");
+
+        assert_eq!(member_dirs(&manifest, &root).unwrap(), vec![root.join("crates").join("a"), root.join("crates").join("b")]);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn rejects_a_glob_pattern_that_isnt_a_trailing_star() {
+        let manifest = parse("[workspace]\nmembers = [\"crates/*/extra\"]\n");
+        assert!(member_dirs(&manifest, Path::new("/ws")).is_err());
+    }
+
+    static NEXT_DIR_ID: AtomicUsize = AtomicUsize::new(0);
+
+    /// Creates a fresh, empty directory under the system temp dir, unique to this test process
+    /// and call, so parallel test runs don't trip over each other's workspace roots.
+    fn temp_workspace_root() -> PathBuf {
+        let id = NEXT_DIR_ID.fetch_add(1, Ordering::SeqCst);
+
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("rusty-tags-workspace-test-{}-{}", std::process::id(), id));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}